@@ -1,13 +1,17 @@
 //! Process management syscalls
 use crate::{
     config::MAX_SYSCALL_NUM,
-    mm::{memory_set::MapPermission, translated_byte_buffer, VirtAddr},
+    loader::get_app_data_by_name,
+    mm::{memory_set::MapPermission, translated_byte_buffer, translated_str, VirtAddr},
     task::{
-        change_program_brk, current_task_map_area, current_task_unmap_area, current_user_token, exit_current_and_run_next, suspend_current_and_run_next, TaskStatus,
+        add_task, change_program_brk, current_task, current_task_map_area,
+        current_task_set_priority, current_task_set_seccomp, current_task_unmap_area,
+        current_user_token, exit_current_and_run_next, suspend_current_and_run_next, TaskStatus,
         current_task_info,
     },
     timer::{get_time_ms, get_time_us},
 };
+use alloc::sync::Arc;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -27,6 +31,25 @@ pub struct TaskInfo {
     time: usize,
 }
 
+/// Copy `value` into user memory at `dst`, splitting the write across however many
+/// page fragments `translated_byte_buffer` returns so it stays correct even when
+/// `T` straddles a page boundary. Returns `-1` if any fragment is unmapped.
+fn copy_to_user<T: Sized>(token: usize, dst: *mut T, value: &T) -> isize {
+    let len = core::mem::size_of::<T>();
+    let src = unsafe { core::slice::from_raw_parts(value as *const T as *const u8, len) };
+    let buffers = translated_byte_buffer(token, dst as *const u8, len);
+    if buffers.iter().map(|b| b.len()).sum::<usize>() != len {
+        return -1;
+    }
+    let mut offset = 0;
+    for buffer in buffers {
+        let chunk = buffer.len();
+        buffer.copy_from_slice(&src[offset..offset + chunk]);
+        offset += chunk;
+    }
+    0
+}
+
 /// task exits and submit an exit code
 pub fn sys_exit(_exit_code: i32) -> ! {
     trace!("kernel: sys_exit");
@@ -41,29 +64,20 @@ pub fn sys_yield() -> isize {
     0
 }
 
-/// YOUR JOB: get time with second and microsecond
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TimeVal`] is splitted by two pages ?
+/// get time with second and microsecond
 pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
     trace!("kernel: sys_get_time");
     let us = get_time_us();
-    let sec_bytes = (us / 1_000_000).to_ne_bytes();
-    let usec_bytes = (us % 1_000_000).to_ne_bytes();
-    let ts = _ts as *const u8;
-    let mut ts_buf = translated_byte_buffer(current_user_token(), ts, 16);
-    if ts_buf.len() == 1 {
-        ts_buf[0][..8].copy_from_slice(&sec_bytes[..]);
-        ts_buf[0][8..].copy_from_slice(&usec_bytes[..]);
-    }
-
-    0
+    let time_val = TimeVal {
+        sec: us / 1_000_000,
+        usec: us % 1_000_000,
+    };
+    copy_to_user(current_user_token(), _ts, &time_val)
 }
 
-/// YOUR JOB: Finish sys_task_info to pass testcases
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TaskInfo`] is splitted by two pages ?
+/// write out the current task's syscall counts, status, and running time
 pub fn sys_task_info(_ti: *mut TaskInfo) -> isize {
-    trace!("kernel: sys_task_info NOT IMPLEMENTED YET!");
+    trace!("kernel: sys_task_info");
     let (syscall_times, stime) = current_task_info();
     let run_time = get_time_ms() - stime;
 
@@ -73,19 +87,7 @@ pub fn sys_task_info(_ti: *mut TaskInfo) -> isize {
         time: run_time,
     };
 
-    let ti_len = core::mem::size_of::<TaskInfo>();
-    let mut ti_buf = translated_byte_buffer(current_user_token(), _ti as *const u8, 2016);
-
-    if ti_buf.len() == 1 {
-        unsafe {
-            core::ptr::copy(
-                &task_info as *const TaskInfo as *const u8,
-                ti_buf[0].as_mut_ptr(),
-                ti_len,
-            );
-        }
-    }
-    0
+    copy_to_user(current_user_token(), _ti, &task_info)
 }
 
 // YOUR JOB: Implement mmap.
@@ -121,6 +123,93 @@ pub fn sys_munmap(_start: usize, _len: usize) -> isize {
     current_task_unmap_area(start_va, end_va)
 }
 
+/// set the current task's stride-scheduling priority, rejecting values below 2
+pub fn sys_set_priority(prio: isize) -> isize {
+    trace!("kernel: sys_set_priority");
+    current_task_set_priority(prio)
+}
+
+/// install a seccomp-style syscall filter on the current task: `mode` 0 clears it,
+/// 1 installs the strict read/write/exit/yield set, 2 installs a caller-supplied
+/// `MAX_SYSCALL_NUM`-byte allow bitmap read from `bitmap_ptr`
+pub fn sys_seccomp(mode: usize, bitmap_ptr: usize) -> isize {
+    trace!("kernel: sys_seccomp");
+    current_task_set_seccomp(mode, bitmap_ptr)
+}
+
+/// get the current task's pid
+pub fn sys_getpid() -> isize {
+    trace!("kernel: sys_getpid");
+    current_task().unwrap().pid.0 as isize
+}
+
+/// spawn a child task that is a copy of the current one, returning 0 in the child
+/// and the child's pid in the parent
+pub fn sys_fork() -> isize {
+    trace!("kernel: sys_fork");
+    let current_task = current_task().unwrap();
+    let new_task = current_task.fork();
+    let new_pid = new_task.pid.0;
+    // a0 carries the syscall's return value to userspace; set the child's to 0
+    // so fork()'s "am I the child" check works there
+    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    trap_cx.x[10] = 0;
+    add_task(new_task);
+    new_pid as isize
+}
+
+/// replace the current task's address space with the named app's ELF image
+pub fn sys_exec(path: *const u8) -> isize {
+    trace!("kernel: sys_exec");
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    if let Some(data) = get_app_data_by_name(path.as_str()) {
+        let task = current_task().unwrap();
+        task.exec(data);
+        0
+    } else {
+        -1
+    }
+}
+
+/// reap a zombie child matching `pid` (or any child, if `pid == -1`), write its
+/// exit code to `exit_code_ptr`, and return its pid. Does not block: returns `-1`
+/// if no child matches and `-2` if matching children exist but none has exited
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    trace!("kernel: sys_waitpid");
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if !task_inner
+        .children
+        .iter()
+        .any(|child| pid == -1 || pid as usize == child.getpid())
+    {
+        // no such child, wait for nothing
+        return -1;
+    }
+    let pair = task_inner.children.iter().enumerate().find(|(_, child)| {
+        child.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == child.getpid())
+    });
+    if let Some((idx, _)) = pair {
+        let child = task_inner.children.remove(idx);
+        // the child's only other owner while it ran was its hart's Processor
+        // (fetch_task had already taken it out of TASK_MANAGER's queue), which
+        // released it on the way to becoming a zombie
+        assert_eq!(Arc::strong_count(&child), 1);
+        let found_pid = child.getpid();
+        let exit_code = child.inner_exclusive_access().exit_code;
+        let token = task_inner.memory_set.token();
+        drop(task_inner);
+        if copy_to_user(token, exit_code_ptr, &exit_code) < 0 {
+            return -1;
+        }
+        found_pid as isize
+    } else {
+        // still has matching children, but none have exited yet
+        -2
+    }
+}
+
 /// change data segment size
 pub fn sys_sbrk(size: i32) -> isize {
     trace!("kernel: sys_sbrk");
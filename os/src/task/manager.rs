@@ -0,0 +1,67 @@
+//! Implementation of [`TaskManager`]
+//!
+//! It is only used to manage processes and schedule process based on ready queue.
+//! Other CPU process monitoring functions are in Processor.
+
+use super::TaskControlBlock;
+use crate::sync::UPSafeCell;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use lazy_static::*;
+
+/// A array of `TaskControlBlock` that is thread-safe
+///
+/// Tasks sit in `ready_queue` in arrival order, but `fetch` doesn't pull from the
+/// front: it picks whichever entry currently has the smallest `stride`, matching
+/// the wraparound-safe comparison documented on [`super::processor::BIG_STRIDE`],
+/// so CPU share tracks `sys_set_priority` instead of FIFO order.
+pub struct TaskManager {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl TaskManager {
+    /// Create an empty TaskManager
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+    /// Add a task to `TaskManager`
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    /// Remove and return the ready task with the smallest stride, advancing its
+    /// stride by its `pass` so the next scheduling decision sees it fall behind
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let mut min_idx = 0;
+        let mut min_stride = self.ready_queue.front()?.inner_exclusive_access().stride;
+        for (idx, task) in self.ready_queue.iter().enumerate().skip(1) {
+            let stride = task.inner_exclusive_access().stride;
+            if (stride.wrapping_sub(min_stride) as i64) < 0 {
+                min_idx = idx;
+                min_stride = stride;
+            }
+        }
+        let task = self.ready_queue.remove(min_idx)?;
+        let mut inner = task.inner_exclusive_access();
+        inner.stride = inner.stride.wrapping_add(inner.pass);
+        drop(inner);
+        Some(task)
+    }
+}
+
+lazy_static! {
+    /// TASK_MANAGER instance through lazy_static!
+    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
+        unsafe { UPSafeCell::new(TaskManager::new()) };
+}
+
+/// Add a task to `TASK_MANAGER`
+pub fn add_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add(task);
+}
+
+/// Take out the ready task with the smallest stride from `TASK_MANAGER`
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().fetch()
+}
@@ -5,16 +5,41 @@
 //! and the replacement and transfer of control flow of different applications are executed.
 
 use super::__switch;
-use super::{fetch_task, TaskStatus};
+use super::{exit_current_and_run_next, fetch_task, TaskStatus};
 use super::{TaskContext, TaskControlBlock};
+use crate::config::MAX_SYSCALL_NUM;
 use crate::sync::UPSafeCell;
 use crate::timer::get_time_ms;
 use crate::trap::TrapContext;
 use crate::syscall::TaskInfo;
-use crate::mm::{MapPermission, VPNRange, VirtAddr};
+use crate::mm::{translated_byte_buffer, MapPermission, VPNRange, VirtAddr};
 use alloc::sync::Arc;
+use alloc::vec;
 use lazy_static::*;
 
+/// Number of harts (CPU cores) this kernel is *built* to manage, one [`Processor`]
+/// each. Only index 0 is ever populated today — see [`hart_id`].
+pub const MAX_HARTS: usize = 4;
+
+/// Stride-scheduling step size. `pass = BIG_STRIDE / priority`, so a task's
+/// `stride` advances faster the lower its priority. Large enough relative to
+/// the maximum `pass` that wraparound stays rare, and `fetch`'s comparison is
+/// wraparound-safe (`(a.stride - b.stride) as i64 < 0`) for the rare case it isn't.
+pub const BIG_STRIDE: usize = 1_000_000;
+
+/// This kernel's hart id.
+///
+/// Real per-hart ids would come out of `tp`, which boot code is supposed to set
+/// to the SBI-assigned hart id before Rust ever runs. No such boot code exists
+/// in this tree: nothing starts a second hart via SBI HSM, and nothing
+/// initializes `tp` on the one hart that does run. Hard-coded to 0, which is
+/// always correct today since this kernel only ever runs on hart 0 — this is
+/// prep for per-hart `Processor`s, not working multi-hart scheduling. Landing
+/// real SMP needs the hart-start (SBI HSM) and `tp`-seeding boot work first.
+pub fn hart_id() -> usize {
+    0
+}
+
 /// Processor management structure
 pub struct Processor {
     ///The task currently executing on the current processor
@@ -50,14 +75,30 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// One [`Processor`] per hart, indexed by [`hart_id`]. Harts would share the
+    /// single global `TASK_MANAGER` ready queue that `fetch_task` already draws
+    /// from, so any hart whose processor goes idle could steal the next ready
+    /// task from it without a dedicated steal step. Sized for [`MAX_HARTS`] in
+    /// anticipation of that, but until boot code actually starts additional
+    /// harts, `hart_id` always returns 0 and only `PROCESSORS[0]` is ever touched.
+    pub static ref PROCESSORS: [UPSafeCell<Processor>; MAX_HARTS] =
+        [(); MAX_HARTS].map(|_| unsafe { UPSafeCell::new(Processor::new()) });
+}
+
+/// Get the calling hart's own `Processor`
+fn current_processor() -> &'static UPSafeCell<Processor> {
+    &PROCESSORS[hart_id()]
 }
 
 ///The main part of process execution and scheduling
 ///Loop `fetch_task` to get the process that needs to run, and switch the process through `__switch`
+///
+///Would run independently per hart once more than one is actually started; for now
+///hart 0 is the only caller, and when the shared ready queue has nothing for it right
+///now, it simply loops back around and tries again instead of warning.
 pub fn run_tasks() {
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
+        let mut processor = current_processor().exclusive_access();
         if let Some(task) = fetch_task() {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
@@ -76,20 +117,18 @@ pub fn run_tasks() {
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
-        } else {
-            warn!("no tasks available in run_tasks");
         }
     }
 }
 
 /// Get current task through take, leaving a None in its place
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    current_processor().exclusive_access().take_current()
 }
 
 /// Get a copy of the current task
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    current_processor().exclusive_access().current()
 }
 
 /// Get the current user token(addr of page table)
@@ -106,9 +145,87 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
         .get_trap_cx()
 }
 
+/// Syscall numbers permitted under [`SeccompMode::Strict`]: read, write, exit, and yield.
+const SECCOMP_STRICT_ALLOWED: [usize; 4] = [63, 64, 93, 124];
+
+/// A per-task syscall filter, installed by `sys_seccomp`. A `Strict` violation
+/// kills the task outright (there is no softer response once a task has opted
+/// into the minimal syscall set); a `Filter` violation just returns `-1`,
+/// matching Linux's `SECCOMP_RET_KILL_PROCESS` vs. `SECCOMP_RET_ERRNO`.
+#[derive(Clone)]
+pub enum SeccompMode {
+    /// No filter installed; every syscall is permitted.
+    Disabled,
+    /// Only [`SECCOMP_STRICT_ALLOWED`] is permitted; anything else kills the task.
+    Strict,
+    /// Caller-supplied bitmap over `MAX_SYSCALL_NUM`; disallowed ids return `-1`.
+    Filter(alloc::vec::Vec<bool>),
+}
+
+impl SeccompMode {
+    fn allows(&self, syscall_id: usize) -> bool {
+        match self {
+            SeccompMode::Disabled => true,
+            SeccompMode::Strict => SECCOMP_STRICT_ALLOWED.contains(&syscall_id),
+            SeccompMode::Filter(bitmap) => bitmap.get(syscall_id).copied().unwrap_or(false),
+        }
+    }
+}
+
+/// Install a seccomp filter on the current task. `mode` follows the `sys_seccomp`
+/// ABI: `0` clears any filter, `1` installs [`SeccompMode::Strict`], `2` installs
+/// [`SeccompMode::Filter`] built from `MAX_SYSCALL_NUM` bytes (one per syscall id,
+/// non-zero means allowed) read from user memory at `bitmap_ptr`.
+pub fn current_task_set_seccomp(mode: usize, bitmap_ptr: usize) -> isize {
+    let task = current_task().unwrap();
+    let new_mode = match mode {
+        0 => SeccompMode::Disabled,
+        1 => SeccompMode::Strict,
+        2 => {
+            let token = task.get_user_token();
+            let buffers = translated_byte_buffer(token, bitmap_ptr as *const u8, MAX_SYSCALL_NUM);
+            if buffers.iter().map(|b| b.len()).sum::<usize>() != MAX_SYSCALL_NUM {
+                return -1;
+            }
+            let mut bitmap = vec![false; MAX_SYSCALL_NUM];
+            let mut offset = 0;
+            for buffer in buffers.iter() {
+                for &byte in buffer.iter() {
+                    bitmap[offset] = byte != 0;
+                    offset += 1;
+                }
+            }
+            SeccompMode::Filter(bitmap)
+        }
+        _ => return -1,
+    };
+    task.inner_exclusive_access().seccomp = new_mode;
+    0
+}
+
+/// Check `syscall_id` against the current task's seccomp filter before it runs.
+/// Returns `Ok(())` to let the syscall proceed, or `Err(errno)` to return `errno`
+/// without running it. A `Strict` violation kills the task instead of returning,
+/// so callers never actually observe that branch's `Err`.
+pub fn current_task_check_seccomp(syscall_id: usize) -> Result<(), isize> {
+    let task = current_task().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    if task_inner.seccomp.allows(syscall_id) {
+        return Ok(());
+    }
+    let is_strict = matches!(task_inner.seccomp, SeccompMode::Strict);
+    drop(task_inner);
+    if is_strict {
+        drop(task);
+        exit_current_and_run_next();
+        unreachable!("exit_current_and_run_next does not return");
+    }
+    Err(-1)
+}
+
 ///Record the number of syscalls of the current task
 pub fn current_task_count_syscall(syscall_id: usize) {
-    let processor = PROCESSOR.exclusive_access();
+    let processor = current_processor().exclusive_access();
     let task = processor.current.as_ref().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     task_inner.task_syscall_times[syscall_id] += 1;
@@ -129,9 +246,23 @@ pub fn current_task_info() -> TaskInfo {
     task_info
 }
 
+/// Set the current task's stride-scheduling priority. Rejects `prio < 2`, since
+/// `pass = BIG_STRIDE / priority` would otherwise make a single task's stride
+/// jump by as much as `BIG_STRIDE`, defeating the point of proportional sharing.
+pub fn current_task_set_priority(prio: isize) -> isize {
+    if prio < 2 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.priority = prio as usize;
+    task_inner.pass = BIG_STRIDE / task_inner.priority;
+    prio
+}
+
 ///Return to idle control flow for new scheduling
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+    let mut processor = current_processor().exclusive_access();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
     unsafe {
@@ -139,38 +270,64 @@ pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     }
 }
 
+/// When set in `_port`, eagerly populate every page of the mapping up front (the
+/// original behaviour), mirroring Linux's `MAP_POPULATE`. Otherwise the mapping is
+/// only recorded in `lazy_areas` and left unpopulated, with pages allocated one at
+/// a time by [`current_task_handle_lazy_page_fault`] on first access.
+const MAP_POPULATE: usize = 1 << 3;
+
+/// Whether `range` overlaps any range already populated in `memory_set` or
+/// reserved (populated or not) in `lazy_areas` -- `mmap` must check both, since
+/// a still-unpopulated reservation has no PTEs for `memory_set.translate` to see.
+fn overlaps_existing(
+    memory_set: &crate::mm::MemorySet,
+    lazy_areas: &[(VPNRange, MapPermission)],
+    range: VPNRange,
+) -> bool {
+    for vpn in range {
+        if let Some(pte) = memory_set.translate(vpn) {
+            if pte.is_valid() {
+                return true;
+            }
+        }
+    }
+    lazy_areas
+        .iter()
+        .any(|(area, _)| area.l.0 < range.r.0 && range.l.0 < area.r.0)
+}
+
 ///current task map memory
 pub fn current_task_mmap(_start: usize, _len: usize, _port: usize) -> isize {
     let start_va = VirtAddr::from(_start);
     let end_va = VirtAddr::from(_start + _len);
-    if start_va.page_offset() != 0 || _port & !0x7 != 0 || _port & 0x7 == 0{
+    if start_va.page_offset() != 0 || _port & !0xf != 0 || _port & 0x7 == 0 {
         return -1;
     }
     let start_vpn = start_va.floor();
     let end_vpn = end_va.ceil();
-
-    let processor = PROCESSOR.exclusive_access();
-    let task = processor.current.as_ref().unwrap();
-    let mut task_inner = task.inner_exclusive_access();
-    let memory_set = &mut task_inner.memory_set;
-
     let vpn_range = VPNRange {
         l: start_vpn,
         r: end_vpn,
     };
 
-    for vpn in vpn_range {
-        if let Some(vpn_map) = memory_set.translate(vpn) {
-            if vpn_map.is_valid() {
-                return -1;
-            }
-        }
+    let processor = current_processor().exclusive_access();
+    let task = processor.current.as_ref().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+
+    if overlaps_existing(&task_inner.memory_set, &task_inner.lazy_areas, vpn_range) {
+        return -1;
     }
 
     let mut map_permission = MapPermission::U;
-    map_permission |= MapPermission::from_bits((_port << 1) as u8).unwrap();
+    map_permission |= MapPermission::from_bits(((_port & 0x7) << 1) as u8).unwrap();
 
-    memory_set.insert_framed_area(start_va, end_va, map_permission);
+    if _port & MAP_POPULATE != 0 {
+        task_inner
+            .memory_set
+            .insert_framed_area(start_va, end_va, map_permission);
+    } else {
+        task_inner.lazy_areas.push((vpn_range, map_permission));
+    }
 
     0
 }
@@ -185,31 +342,88 @@ pub fn current_task_munmap(_start: usize, _len: usize) -> isize {
     let start_vpn = start_va.floor();
     let end_vpn = end_va.ceil();
 
-    let processor = PROCESSOR.exclusive_access();
+    let processor = current_processor().exclusive_access();
     let task = processor.current.as_ref().unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    let memory_set = &mut task_inner.memory_set;
 
     let vpn_range = VPNRange {
         l: start_vpn,
         r: end_vpn,
     };
 
+    // a lazy reservation is recorded under its own original range, and
+    // faulting pages in along the way never touches lazy_areas, so the whole
+    // reservation -- populated or not -- is found this way. A MAP_POPULATE
+    // mapping has no lazy_areas entry and must instead be fully mapped
+    let lazy_idx = task_inner
+        .lazy_areas
+        .iter()
+        .position(|(range, _)| range.l == start_vpn && range.r == end_vpn);
+    if lazy_idx.is_none()
+        && !vpn_range.into_iter().all(|vpn| {
+            matches!(task_inner.memory_set.translate(vpn), Some(pte) if pte.is_valid())
+        })
+    {
+        return -1;
+    }
+
     for vpn in vpn_range {
-        let pte = memory_set.translate(vpn);
-        if pte.is_none() {
-            return -1;
-        }
-        else {
-            if let Some(vpn_map) = pte {
-                if !vpn_map.is_valid() {
-                    return -1;
-                }
+        // faulted-in pages each live in their own single-page MapArea (one per
+        // current_task_handle_lazy_page_fault call), so tear them down
+        // individually instead of assuming one contiguous area to remove
+        if let Some(pte) = task_inner.memory_set.translate(vpn) {
+            if pte.is_valid() {
+                task_inner.memory_set.remove_area_with_start_vpn(vpn);
             }
         }
     }
 
-    memory_set.remove_area_with_start_vpn(start_vpn);
+    if let Some(idx) = lazy_idx {
+        task_inner.lazy_areas.remove(idx);
+    }
+
+    0
+}
+
+/// Handle a page fault for the current task: if `fault_va` falls inside a lazily
+/// mapped (unpopulated) mmap region, allocate a single zeroed frame and map it at
+/// that page on first access. Returns `-1` if the fault isn't inside any lazy
+/// region, or if `is_write` is set but the region's permissions are read-only.
+///
+/// Never mutates `lazy_areas`: the reservation it matched against keeps covering
+/// its full original range regardless of how many of its pages get faulted in
+/// here, so `current_task_munmap` can always find and tear down the whole thing.
+pub fn current_task_handle_lazy_page_fault(fault_va: VirtAddr, is_write: bool) -> isize {
+    let fault_vpn = fault_va.floor();
+
+    let processor = current_processor().exclusive_access();
+    let task = processor.current.as_ref().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+
+    let permission = match task_inner
+        .lazy_areas
+        .iter()
+        .find(|(range, _)| range.l.0 <= fault_vpn.0 && fault_vpn.0 < range.r.0)
+    {
+        Some((_, permission)) => *permission,
+        None => return -1,
+    };
+    if is_write && !permission.contains(MapPermission::W) {
+        return -1;
+    }
+
+    // already faulted in by an earlier access to the same page
+    if let Some(pte) = task_inner.memory_set.translate(fault_vpn) {
+        if pte.is_valid() {
+            return 0;
+        }
+    }
+
+    let frame_start = VirtAddr::from(fault_vpn.0 << 12);
+    let frame_end = VirtAddr::from((fault_vpn.0 + 1) << 12);
+    task_inner
+        .memory_set
+        .insert_framed_area(frame_start, frame_end, permission);
 
     0
 }
@@ -0,0 +1,256 @@
+//! Types related to task (process) management
+
+use super::pid::{pid_alloc, KernelStack, PidHandle};
+use super::processor::{SeccompMode, BIG_STRIDE};
+use super::TaskContext;
+use crate::config::{MAX_SYSCALL_NUM, TRAP_CONTEXT};
+use crate::mm::{MapPermission, MemorySet, PhysPageNum, VPNRange, VirtAddr, KERNEL_SPACE};
+use crate::sync::UPSafeCell;
+use crate::trap::{trap_handler, TrapContext};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cell::RefMut;
+
+/// Default stride-scheduling priority a freshly created task starts at
+const DEFAULT_PRIORITY: usize = 16;
+
+/// Task control block structure
+///
+/// Directly holds the parts that never change once a process is created.
+pub struct TaskControlBlock {
+    /// Process identifier
+    pub pid: PidHandle,
+
+    /// This process's kernel stack, mapped for as long as `pid` lives
+    pub kernel_stack: KernelStack,
+
+    /// Mutable inner state, reached only through `inner_exclusive_access`
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+/// The parts of a [`TaskControlBlock`] that change as the process runs,
+/// guarded by `UPSafeCell` so callers get mutual exclusion instead of a lock.
+pub struct TaskControlBlockInner {
+    /// Physical page holding this task's trap context
+    pub trap_cx_ppn: PhysPageNum,
+
+    /// Size of the application's address space below which `memory_set` is valid
+    pub base_size: usize,
+
+    /// Saved task context, switched to/from by `__switch`
+    pub task_cx: TaskContext,
+
+    /// Current execution status
+    pub task_status: TaskStatus,
+
+    /// Application address space
+    pub memory_set: MemorySet,
+
+    /// Parent process, if any. `Weak` so the parent's `Arc` count isn't kept
+    /// alive by its children.
+    pub parent: Option<Weak<TaskControlBlock>>,
+
+    /// Child processes spawned by `fork`
+    pub children: Vec<Arc<TaskControlBlock>>,
+
+    /// Exit code that `sys_waitpid` reports to the parent when it reaps this task
+    pub exit_code: i32,
+
+    /// Heap bottom, for `sys_sbrk` bookkeeping
+    pub heap_bottom: usize,
+
+    /// Current program break, for `sys_sbrk` bookkeeping
+    pub program_brk: usize,
+
+    /// Count of each syscall this task has made, reported by `sys_task_info`
+    pub task_syscall_times: [u32; MAX_SYSCALL_NUM],
+
+    /// Time this task was first scheduled, for `sys_task_info`'s running time
+    pub task_stime: usize,
+
+    /// Stride-scheduling priority set by `sys_set_priority` (always >= 2)
+    pub priority: usize,
+
+    /// Stride accumulator; the scheduler always runs the ready task with the
+    /// smallest `stride`, then advances it by `pass`
+    pub stride: usize,
+
+    /// `BIG_STRIDE / priority`, added to `stride` every time this task runs
+    pub pass: usize,
+
+    /// Installed seccomp-style syscall filter, inherited by `fork`
+    pub seccomp: SeccompMode,
+
+    /// Reservations made by `mmap` that are awaiting first access: `(range,
+    /// permission)`. An entry always covers the exact range `mmap` reserved it
+    /// with -- faulting a page in populates `memory_set` but never touches this
+    /// list -- so `munmap` can always find and tear down the whole reservation
+    /// by its original range, regardless of how much of it has been faulted in.
+    pub lazy_areas: Vec<(VPNRange, MapPermission)>,
+}
+
+impl TaskControlBlockInner {
+    /// Mutable reference to this task's trap context
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
+
+    /// This task's user token (satp value)
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.token()
+    }
+
+    /// Whether this task has exited and is waiting to be reaped by `sys_waitpid`
+    pub fn is_zombie(&self) -> bool {
+        self.task_status == TaskStatus::Zombie
+    }
+}
+
+impl TaskControlBlock {
+    /// Mutable, exclusive access to this task's inner state
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    /// This task's user token (satp value)
+    pub fn get_user_token(&self) -> usize {
+        self.inner_exclusive_access().get_user_token()
+    }
+
+    /// This task's pid
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+
+    /// Build the very first process (the app loader's initproc) from an ELF image
+    pub fn new(elf_data: &[u8]) -> Self {
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+        let task_control_block = Self {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: user_sp,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set,
+                    parent: None,
+                    children: Vec::new(),
+                    exit_code: 0,
+                    heap_bottom: user_sp,
+                    program_brk: user_sp,
+                    task_syscall_times: [0; MAX_SYSCALL_NUM],
+                    task_stime: 0,
+                    priority: DEFAULT_PRIORITY,
+                    stride: 0,
+                    pass: BIG_STRIDE / DEFAULT_PRIORITY,
+                    seccomp: SeccompMode::Disabled,
+                    lazy_areas: Vec::new(),
+                })
+            },
+        };
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            kernel_stack_top,
+            trap_handler as usize,
+        );
+        task_control_block
+    }
+
+    /// Replace this task's address space in place with `elf_data`'s ELF image.
+    /// Pid, kernel stack, and parent/children links are untouched: `exec`
+    /// changes what a process runs, not which process it is.
+    pub fn exec(&self, elf_data: &[u8]) {
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set = memory_set;
+        inner.trap_cx_ppn = trap_cx_ppn;
+        inner.base_size = user_sp;
+        inner.heap_bottom = user_sp;
+        inner.program_brk = user_sp;
+        // the old address space's mmap bookkeeping doesn't apply to the new one
+        inner.lazy_areas.clear();
+        let trap_cx = inner.get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            self.kernel_stack.get_top(),
+            trap_handler as usize,
+        );
+    }
+
+    /// Deep-copy this task into a freshly allocated child: a new pid, kernel
+    /// stack, and page table, but a `MemorySet` whose framed areas mirror the
+    /// parent's. The child's trap context's `a0` is left as the parent's here;
+    /// `sys_fork` overwrites it with 0 once the child is built.
+    pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+        let mut parent_inner = self.inner_exclusive_access();
+        let memory_set = MemorySet::from_existing_user(&parent_inner.memory_set);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+        let task_control_block = Arc::new(TaskControlBlock {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: parent_inner.base_size,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set,
+                    parent: Some(Arc::downgrade(self)),
+                    children: Vec::new(),
+                    exit_code: 0,
+                    heap_bottom: parent_inner.heap_bottom,
+                    program_brk: parent_inner.program_brk,
+                    task_syscall_times: [0; MAX_SYSCALL_NUM],
+                    task_stime: 0,
+                    priority: parent_inner.priority,
+                    stride: 0,
+                    pass: parent_inner.pass,
+                    seccomp: parent_inner.seccomp.clone(),
+                    lazy_areas: parent_inner.lazy_areas.clone(),
+                })
+            },
+        });
+        parent_inner.children.push(Arc::clone(&task_control_block));
+        // the kernel stack is brand new, so the copied TrapContext's kernel_sp
+        // still points at the parent's; fix it up to the child's own stack
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        trap_cx.kernel_sp = kernel_stack_top;
+        task_control_block
+    }
+}
+
+/// Execution status of a [`TaskControlBlock`]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// Ready to run, sitting in the `TASK_MANAGER` queue
+    Ready,
+    /// Currently running on some hart's `Processor`
+    Running,
+    /// Exited and waiting for its parent to reap it via `sys_waitpid`
+    Zombie,
+}
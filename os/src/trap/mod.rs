@@ -0,0 +1,105 @@
+//! Trap handling functionality
+//!
+//! All traps go through `__alltraps` (see `trap.S`) into [`trap_handler`], which
+//! dispatches on the cause: syscalls, the timer interrupt, and the page faults
+//! produced by lazily-populated mmap regions are handled here. Any other page
+//! fault and illegal instructions kill the offending task; any other trap cause
+//! panics the kernel.
+
+mod context;
+
+pub use context::TrapContext;
+
+use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::syscall::syscall;
+use crate::task::{
+    current_task_handle_lazy_page_fault, current_trap_cx, current_user_token,
+    exit_current_and_run_next, suspend_current_and_run_next,
+};
+use crate::timer::set_next_trigger;
+use core::arch::{asm, global_asm};
+use riscv::register::{
+    scause::{self, Exception, Interrupt, Trap},
+    stval,
+};
+
+global_asm!(include_str!("trap.S"));
+
+/// Entered via `__alltraps` with the trapping task's trap context already
+/// saved to its designated page; dispatches on the trap cause and never
+/// returns (falls through to [`trap_return`]).
+#[no_mangle]
+pub fn trap_handler() -> ! {
+    let scause = scause::read();
+    let stval = stval::read();
+    match scause.cause() {
+        Trap::Exception(Exception::UserEnvCall) => {
+            let mut cx = current_trap_cx();
+            cx.sepc += 4;
+            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            // syscall() may have replaced the current task's address space
+            // (sys_exec), which moves its trap context to a new physical page,
+            // so re-fetch the trap context rather than reuse `cx`
+            cx = current_trap_cx();
+            cx.x[10] = result as usize;
+        }
+        Trap::Exception(Exception::StoreFault)
+        | Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::LoadFault)
+        | Trap::Exception(Exception::LoadPageFault) => {
+            let is_write = matches!(
+                scause.cause(),
+                Trap::Exception(Exception::StoreFault)
+                    | Trap::Exception(Exception::StorePageFault)
+            );
+            // a fault inside a still-lazy mmap region is expected: populate
+            // the single faulting page and resume. Anything else is a real
+            // bad access and kills the task, same as before mmap existed.
+            if current_task_handle_lazy_page_fault(stval.into(), is_write) != 0 {
+                println!(
+                    "[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
+                    stval,
+                    current_trap_cx().sepc
+                );
+                exit_current_and_run_next();
+            }
+        }
+        Trap::Exception(Exception::IllegalInstruction) => {
+            println!("[kernel] IllegalInstruction in application, kernel killed it.");
+            exit_current_and_run_next();
+        }
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            set_next_trigger();
+            suspend_current_and_run_next();
+        }
+        _ => {
+            panic!(
+                "Unsupported trap {:?}, stval = {:#x}!",
+                scause.cause(),
+                stval
+            );
+        }
+    }
+    trap_return();
+}
+
+/// Restore the current task's trap context and `sret` back to user mode
+#[no_mangle]
+pub fn trap_return() -> ! {
+    extern "C" {
+        fn __alltraps();
+        fn __restore();
+    }
+    let user_satp = current_user_token();
+    let restore_va = __restore as usize - __alltraps as usize + TRAMPOLINE;
+    unsafe {
+        asm!(
+            "fence.i",
+            "jr {restore_va}",
+            restore_va = in(reg) restore_va,
+            in("a0") TRAP_CONTEXT,
+            in("a1") user_satp,
+            options(noreturn)
+        );
+    }
+}